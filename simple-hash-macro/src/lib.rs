@@ -1,41 +1,84 @@
 use proc_macro::TokenStream;
-use proc_macro2::Span;
 use syn;
-use quote::quote;
+use quote::{quote, format_ident};
+
+fn fields_update_code(fields: &syn::Fields) -> Vec<proc_macro2::TokenStream> {
+    match fields {
+        syn::Fields::Named(fields) => {
+            fields.named.iter().map(|f| {
+                let ident = f.ident.clone().unwrap();
+                quote! { self.#ident.update(h); }
+            }).collect()
+        },
+        syn::Fields::Unnamed(fields) => {
+            fields.unnamed.iter().enumerate().map(|(i, _)| {
+                let index = syn::Index::from(i);
+                quote! { self.#index.update(h); }
+            }).collect()
+        },
+        syn::Fields::Unit => vec![],
+    }
+}
+
+fn variant_arm_code(enum_name: &syn::Ident, index: u32, variant: &syn::Variant) -> proc_macro2::TokenStream {
+    let variant_name = &variant.ident;
+    match &variant.fields {
+        syn::Fields::Named(fields) => {
+            let idents: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+            quote! {
+                #enum_name::#variant_name { #(#idents),* } => {
+                    (#index as u32).update(h);
+                    #(#idents.update(h);)*
+                }
+            }
+        },
+        syn::Fields::Unnamed(fields) => {
+            let idents: Vec<_> = fields.unnamed.iter().enumerate().map(|(i, _)| format_ident!("field_{}", i)).collect();
+            quote! {
+                #enum_name::#variant_name(#(#idents),*) => {
+                    (#index as u32).update(h);
+                    #(#idents.update(h);)*
+                }
+            }
+        },
+        syn::Fields::Unit => {
+            quote! {
+                #enum_name::#variant_name => {
+                    (#index as u32).update(h);
+                }
+            }
+        },
+    }
+}
 
 #[proc_macro_derive(Hashable)]
 pub fn hashable_derive(input: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
 
     let name = &ast.ident;
-    let fields = match &ast.data {
-        syn::Data::Struct(syn::DataStruct {
-            fields: syn::Fields::Named(fields),
-            ..
-        }) => {
-            &fields.named
+    let generics = &ast.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let update_body = match &ast.data {
+        syn::Data::Struct(syn::DataStruct { fields, .. }) => {
+            let fields_code = fields_update_code(fields);
+            quote! { #(#fields_code)* }
         },
-        syn::Data::Struct(syn::DataStruct {
-            fields: syn::Fields::Unnamed(fields),
-            ..
-        }) => {
-            &fields.unnamed
+        syn::Data::Enum(data) => {
+            let arms: Vec<_> = data.variants.iter().enumerate().map(|(i, v)| variant_arm_code(name, i as u32, v)).collect();
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
         },
-        _ => panic!("Expected a struct"),
+        _ => panic!("Expected a struct or an enum"),
     };
-    let fields: Vec<syn::Ident> = fields.iter().enumerate().map(|(i, f)| f.ident.clone().unwrap_or_else(|| syn::Ident::new(&i.to_string(), Span::call_site()))).collect();
-    let generics = &ast.generics;
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let fields_code: Vec<_> = fields.into_iter().map(|f| {
-        quote!{
-            self.#f.update(h);
-        }
-    }).collect();
     let ret = quote! {
        impl #impl_generics simple_hash::Hashable for #name #ty_generics #where_clause {
-           fn update<H: Hasher>(&self, h: &mut H) {
-               #(#fields_code)*
+           fn update_fields<H: Hasher>(&self, h: &mut H) {
+               #update_body
            }
        }
     };
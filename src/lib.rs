@@ -2,13 +2,23 @@
 //!
 //! This crates defines two traits: [Hasher] and [Hashable].
 //!
-//! The first represents an hashing algorithm and state, and is currently implemented
-//! for [sha2::Sha256].
+//! The first represents an hashing algorithm and state, and is blanket-implemented
+//! for any [digest::Digest] that is also `Clone` (e.g. [sha2::Sha256], [sha2::Sha512], ...).
 //!
 //! The second is implemented for any rust value that needs to be hashed.
 //! An Helper derive macro with the same name is provided to avoid boilerplate.
 //!
-//! The current set of std types that implement [Hashable] is limited. PRs are welcome.
+//! [HasherBuilder] separates an algorithm from how it's initialized, so a
+//! single (possibly keyed/seeded) builder can be reused to hash many values
+//! via [Hashable::digest_with], without re-specifying the algorithm each time.
+//!
+//! [Hashable] is also implemented for common composite types: tuples up to
+//! arity 12, `[T; N]`, `Option`/`Result`, and the smart pointers `&T`, `Box`,
+//! `Rc` and `Arc`.
+//!
+//! A type that already knows its digest (cached, memoized, received over the
+//! wire, ...) can skip re-hashing its fields by overriding
+//! [Hashable::precomputed]; see [Cached] for a ready-made wrapper.
 //!
 //! Example use:
 //!
@@ -26,11 +36,10 @@
 //!     c: vec![0,1,2,3],
 //! };
 //! let res = foo.digest::<sha2::Sha256>();
-//! assert_eq!(res, hex_literal::hex!("929863ce588951eae0cc88755216f96951d431e7d15adbb836d8f1960bb65a9d"));
+//! assert_eq!(res.as_slice(), &hex_literal::hex!("778832e1c70fcb0153d8609edbb74d4a5593f7aee9173cbec94f0fec9505478c")[..]);
 //! ```
 //!
-use sha2::Sha256;
-use sha2::Digest;
+use digest::Digest;
 use byteorder::{LittleEndian, WriteBytesExt};
 use paste::paste;
 
@@ -44,35 +53,111 @@ pub trait Hasher {
 }
 
 pub trait Hashable {
-    fn update<H: Hasher>(&self, h: &mut H);
+    /// Feeds `self`'s fields into `h`, or — if [Hashable::precomputed] returns
+    /// `Some` — feeds the precomputed digest instead of recursing into them.
+    /// Implementors should not override this; implement [Hashable::update_fields] instead.
+    fn update<H: Hasher>(&self, h: &mut H) {
+        match self.precomputed() {
+            Some(digest) => {
+                (digest.len() as u64).update(h);
+                h.update(digest);
+            },
+            None => self.update_fields(h),
+        }
+    }
+    fn update_fields<H: Hasher>(&self, h: &mut H);
+    /// Lets a type that already knows its digest (e.g. one cached or received
+    /// over the wire) hand back its raw bytes instead of being re-hashed
+    /// field by field. See [Cached].
+    fn precomputed(&self) -> Option<&[u8]> {
+        None
+    }
     fn digest<H: Hasher>(&self) -> <H as Hasher>::Output where Self: Sized {
         H::digest(self)
     }
+    fn digest_with<B: HasherBuilder>(&self, builder: &B) -> <B::Hasher as Hasher>::Output where Self: Sized {
+        let mut h = builder.build();
+        self.update(&mut h);
+        h.finish()
+    }
+}
+
+/// Wraps a value together with its already-computed digest, so that hashing
+/// the wrapper feeds the cached bytes straight into the hasher instead of
+/// recursing into `T`. Useful when a value's hash is memoized or was
+/// received pre-computed (e.g. from a database or the wire).
+pub struct Cached<T> {
+    value: T,
+    digest: Vec<u8>,
+}
+
+impl<T> Cached<T> {
+    pub fn new(value: T, digest: impl Into<Vec<u8>>) -> Self {
+        Cached { value, digest: digest.into() }
+    }
+    pub fn value(&self) -> &T {
+        &self.value
+    }
 }
 
-impl Hasher for Sha256 {
-    type Output = [u8; 32];
+impl<T> Hashable for Cached<T> {
+    fn update_fields<H: Hasher>(&self, _h: &mut H) {
+        unreachable!("Cached::precomputed always returns Some, so update_fields is never called")
+    }
+    fn precomputed(&self) -> Option<&[u8]> {
+        Some(&self.digest)
+    }
+}
 
-    fn update<D: AsRef<[u8]>>(&mut self, data: D) {
+/// Separates a hashing algorithm from how its initial state is constructed,
+/// so the same builder (e.g. a keyed/seeded one) can be reused to hash many
+/// values via [Hashable::digest_with].
+pub trait HasherBuilder {
+    type Hasher: Hasher;
+    fn build(&self) -> Self::Hasher;
+}
+
+/// A [HasherBuilder] that just builds a fresh, default-initialized `D`, for
+/// callers who don't need a custom construction.
+pub struct DefaultBuilder<D>(std::marker::PhantomData<D>);
+
+impl<D> DefaultBuilder<D> {
+    pub fn new() -> Self {
+        DefaultBuilder(std::marker::PhantomData)
+    }
+}
+
+impl<D> Default for DefaultBuilder<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest + Clone> HasherBuilder for DefaultBuilder<D> {
+    type Hasher = D;
+    fn build(&self) -> D {
+        D::new()
+    }
+}
+
+impl<D: Digest + Clone> Hasher for D {
+    type Output = digest::Output<D>;
+
+    fn update<T: AsRef<[u8]>>(&mut self, data: T) {
         Digest::update(self, data);
     }
     fn finish(self) -> Self::Output {
-        let res = self.finalize();
-        let mut out = [0; 32];
-        for i in 0..res.len() {
-            out[i] = res[i];
-        }
-        out
+        self.finalize()
     }
     fn digest<H: Hashable>(data: &H) -> Self::Output {
-        let mut sha = Sha256::new();
-        data.update(&mut sha);
-        sha.finish()
+        let mut d = D::new();
+        data.update(&mut d);
+        d.finish()
     }
 }
 
 impl Hashable for u8 {
-    fn update<H: Hasher>(&self, h: &mut H) {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
         let mut buf = [0u8; std::mem::size_of::<u8>()];
         let mut b = &mut buf[..];
         b.write_u8(*self).unwrap();
@@ -80,7 +165,7 @@ impl Hashable for u8 {
     }
 }
 impl Hashable for bool {
-    fn update<H: Hasher>(&self, h: &mut H) {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
         let mut buf = [0u8; std::mem::size_of::<u8>()];
         let mut b = &mut buf[..];
         b.write_u8(*self as u8).unwrap();
@@ -88,7 +173,7 @@ impl Hashable for bool {
     }
 }
 impl Hashable for i8 {
-    fn update<H: Hasher>(&self, h: &mut H) {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
         let mut buf = [0u8; std::mem::size_of::<u8>()];
         let mut b = &mut buf[..];
         b.write_i8(*self).unwrap();
@@ -99,7 +184,7 @@ impl Hashable for i8 {
 macro_rules! impl_hashable_for {
     ($t:ty) => {
         impl crate::Hashable for $t {
-            fn update<H: Hasher>(&self, h: &mut H) {
+            fn update_fields<H: Hasher>(&self, h: &mut H) {
                 let mut buf = [0u8; std::mem::size_of::<$t>()];
                 let mut b = &mut buf[..];
                 paste! {
@@ -119,21 +204,110 @@ impl_hashable_for!(i64);
 impl_hashable_for!(u64);
 
 
+// Collections are length-prefixed before their elements are hashed, so that
+// the encoding is prefix-free: two fields of different length can never
+// "borrow" bytes from one another and collide. Any future impl for a
+// variable-length collection should follow the same contract.
 impl<T: Hashable> Hashable for Vec<T> {
-    fn update<H: Hasher>(&self, h: &mut H) {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (self.len() as u64).update(h);
         for t in self {
             t.update(h);
         }
     }
 }
 impl Hashable for String {
-    fn update<H: Hasher>(&self, h: &mut H) {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (self.len() as u64).update(h);
         for t in self.as_bytes() {
             t.update(h);
         }
     }
 }
 
+impl<T: Hashable, const N: usize> Hashable for [T; N] {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        for t in self {
+            t.update(h);
+        }
+    }
+}
+
+impl<T: Hashable + ?Sized> Hashable for &T {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (**self).update(h);
+    }
+}
+impl<T: Hashable + ?Sized> Hashable for Box<T> {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (**self).update(h);
+    }
+}
+impl<T: Hashable + ?Sized> Hashable for std::rc::Rc<T> {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (**self).update(h);
+    }
+}
+impl<T: Hashable + ?Sized> Hashable for std::sync::Arc<T> {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        (**self).update(h);
+    }
+}
+
+// `Option`/`Result` are sum types: following the same rule the derive macro
+// uses for enums, a one-byte tag is hashed before the payload so that e.g.
+// `None` and `Some(0u8)` never collide.
+impl<T: Hashable> Hashable for Option<T> {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        match self {
+            None => (0u8).update(h),
+            Some(t) => {
+                (1u8).update(h);
+                t.update(h);
+            }
+        }
+    }
+}
+impl<T: Hashable, E: Hashable> Hashable for Result<T, E> {
+    fn update_fields<H: Hasher>(&self, h: &mut H) {
+        match self {
+            Ok(t) => {
+                (0u8).update(h);
+                t.update(h);
+            }
+            Err(e) => {
+                (1u8).update(h);
+                e.update(h);
+            }
+        }
+    }
+}
+
+macro_rules! impl_hashable_for_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: Hashable),+> Hashable for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn update_fields<HH: Hasher>(&self, h: &mut HH) {
+                let ($($name,)+) = self;
+                $($name.update(h);)+
+            }
+        }
+    };
+}
+
+impl_hashable_for_tuple!(A);
+impl_hashable_for_tuple!(A B);
+impl_hashable_for_tuple!(A B C);
+impl_hashable_for_tuple!(A B C D);
+impl_hashable_for_tuple!(A B C D E);
+impl_hashable_for_tuple!(A B C D E F);
+impl_hashable_for_tuple!(A B C D E F G);
+impl_hashable_for_tuple!(A B C D E F G H);
+impl_hashable_for_tuple!(A B C D E F G H I);
+impl_hashable_for_tuple!(A B C D E F G H I J);
+impl_hashable_for_tuple!(A B C D E F G H I J K);
+impl_hashable_for_tuple!(A B C D E F G H I J K L);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,7 +322,13 @@ mod tests {
     #[test]
     fn test_u8() {
         let res = (9u8).digest::<sha2::Sha256>();
-        assert_eq!(res, hex_literal::hex!("2b4c342f5433ebe591a1da77e013d1b72475562d48578dca8b84bac6651c3cb9"));
+        assert_eq!(res.as_slice(), &hex_literal::hex!("2b4c342f5433ebe591a1da77e013d1b72475562d48578dca8b84bac6651c3cb9")[..]);
+    }
+    #[test]
+    fn test_vec_length_prefix_avoids_collision() {
+        let a = (vec![1u8], vec![2u8, 3]);
+        let b = (vec![1u8, 2], vec![3u8]);
+        assert_ne!(a.digest::<sha2::Sha256>(), b.digest::<sha2::Sha256>());
     }
     #[test]
     fn test_derive() {
@@ -158,6 +338,83 @@ mod tests {
             c: vec![0,1,2,3],
         };
         let res = foo.digest::<sha2::Sha256>();
-        assert_eq!(res, hex_literal::hex!("929863ce588951eae0cc88755216f96951d431e7d15adbb836d8f1960bb65a9d"));
+        assert_eq!(res.as_slice(), &hex_literal::hex!("778832e1c70fcb0153d8609edbb74d4a5593f7aee9173cbec94f0fec9505478c")[..]);
+    }
+
+    #[derive(Hashable)]
+    struct Pair(u8, u16);
+    #[test]
+    fn test_tuple_struct_derive() {
+        let res = Pair(8, 99).digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("41b9d432df4336370271c603179dd670400113d978dcb7746a09594b31c5a05d")[..]);
+    }
+
+    #[derive(Hashable)]
+    enum Shape {
+        Circle(u32),
+        Square { side: u32 },
+        Point,
+    }
+    #[test]
+    fn test_enum_derive() {
+        let res = Shape::Circle(5).digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("2be2196b9c19b0913b11d708d2550cdf0f5b0106c4ae0eec2aa07d2b243c7268")[..]);
+        let res = Shape::Square { side: 5 }.digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("eca75f8497701d6223817cde38bf42cdd1124e01ef6b705bcfe9a584f7b42f0f")[..]);
+        let res = Shape::Point.digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("26b25d457597a7b0463f9620f666dd10aa2c4373a505967c7c8d70922a2d6ece")[..]);
+    }
+
+    #[test]
+    fn test_tuple_matches_equivalent_struct() {
+        let t = (8u8, 99u16, vec![0u32, 1, 2, 3]);
+        let res = t.digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("778832e1c70fcb0153d8609edbb74d4a5593f7aee9173cbec94f0fec9505478c")[..]);
+    }
+
+    #[test]
+    fn test_option_tag_distinguishes_none_from_zero() {
+        let none: Option<u8> = None;
+        let some_zero: Option<u8> = Some(0);
+        assert_ne!(none.digest::<sha2::Sha256>(), some_zero.digest::<sha2::Sha256>());
+    }
+
+    #[test]
+    fn test_result_tag_distinguishes_ok_from_err() {
+        let ok: Result<u8, u8> = Ok(0);
+        let err: Result<u8, u8> = Err(0);
+        assert_ne!(ok.digest::<sha2::Sha256>(), err.digest::<sha2::Sha256>());
+    }
+
+    #[test]
+    fn test_sha512() {
+        let res = (9u8).digest::<sha2::Sha512>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("f27b5bf8d35ea2bbbb6c0f9fef89d883415b5adbd6a84030cb1f35e6a6c026e65c60fb99f562f7eb9f77f3dec5001473441d2c5586b54d9b999cf4bd790e4c56")[..]);
+    }
+
+    #[test]
+    fn test_array() {
+        let res = [1u8, 2, 3].digest::<sha2::Sha256>();
+        assert_eq!(res.as_slice(), &hex_literal::hex!("039058c6f2c0cb492c533b0a4d14ef77cc0f78abccced5287d84a1a2011cfb81")[..]);
+    }
+
+    #[test]
+    fn test_box_delegates_to_inner() {
+        let boxed: Box<u8> = Box::new(9);
+        assert_eq!(boxed.digest::<sha2::Sha256>(), (9u8).digest::<sha2::Sha256>());
+    }
+
+    #[test]
+    fn test_digest_with_default_builder_matches_digest() {
+        let res_default = (9u8).digest::<sha2::Sha256>();
+        let res_builder = (9u8).digest_with(&DefaultBuilder::<sha2::Sha256>::new());
+        assert_eq!(res_builder, res_default);
+    }
+
+    #[test]
+    fn test_cached_matches_length_prefixed_vec_of_bytes() {
+        let bytes = vec![1u8, 2, 3];
+        let cached = Cached::new((), bytes.clone());
+        assert_eq!(cached.digest::<sha2::Sha256>(), bytes.digest::<sha2::Sha256>());
     }
 }